@@ -0,0 +1,92 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    sync::Arc,
+};
+
+use dotenv_codegen::dotenv;
+use rspotify::{
+    clients::mutex::Mutex, prelude::OAuthClient, scopes, AuthCodeSpotify, Config, Credentials,
+    OAuth,
+};
+
+use crate::config;
+
+/// Local address the callback server binds to, to capture the OAuth redirect.
+const CALLBACK_BIND_ADDR: &str = "127.0.0.1:8888";
+
+/// Redirect URI registered with the Spotify application. This must match exactly, so it stays
+/// `localhost` (not `127.0.0.1`) to line up with the app registration from before the callback
+/// server existed.
+const REDIRECT_URI: &str = "http://localhost:8888/callback";
+
+pub async fn spotify_auth() -> AuthCodeSpotify {
+    // let creds = Credentials::from_env().expect("Failed to get app credentials");
+    let creds = Credentials::new(
+        dotenv!("RSPOTIFY_CLIENT_ID"),
+        dotenv!("RSPOTIFY_CLIENT_SECRET"),
+    );
+    let oauth = OAuth {
+        redirect_uri: REDIRECT_URI.to_string(),
+        scopes: scopes!(
+            "user-read-currently-playing",
+            "user-read-playback-state",
+            "playlist-read-private",
+            "playlist-modify-private",
+            "user-library-modify",
+            "user-library-read"
+        ),
+        ..Default::default()
+    };
+    let config = Config {
+        token_cached: true,
+        token_refreshing: true,
+        cache_path: config::base_dir().join("token_cache.json"),
+        // pagination_chunks: 100,
+        ..Default::default()
+    };
+    let mut spotify = AuthCodeSpotify::with_config(creds, oauth, config);
+    if !spotify.config.cache_path.exists() {
+        println!("Opening a browser window to log in to Spotify...");
+        let url = spotify.get_authorize_url(false).unwrap();
+        webbrowser::open(&url).expect("Error opening browser for authorization");
+        let code = capture_redirect_code().expect("Error capturing OAuth redirect");
+        spotify
+            .request_token(&code)
+            .await
+            .expect("Couldn't authenticate successfully");
+    }
+    spotify.token = Arc::new(Mutex::new(spotify.read_token_cache(true).await.unwrap()));
+    spotify
+}
+
+/// Blocks until Spotify redirects the browser back to `/callback`, then returns the `code`
+/// query parameter from that request.
+///
+/// This replaces the old copy-paste flow: we bind [`CALLBACK_BIND_ADDR`] ourselves, accept the
+/// single incoming connection the redirect produces, and hand the browser a small confirmation
+/// page before shutting the listener down.
+fn capture_redirect_code() -> std::io::Result<String> {
+    let listener = std::net::TcpListener::bind(CALLBACK_BIND_ADDR)?;
+    let (mut stream, _) = listener.accept()?;
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let code = path
+        .split_once('?')
+        .and_then(|(_, query)| query.split('&').find_map(|pair| pair.strip_prefix("code=")))
+        .map(str::to_string)
+        .ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "No code in redirect")
+        })?;
+
+    let body = "<html><body>Logged in to Spotify, you can close this tab.</body></html>";
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: text/html\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes())?;
+
+    Ok(code)
+}
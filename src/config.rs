@@ -0,0 +1,73 @@
+use std::{fs, path::PathBuf};
+
+use directories::BaseDirs;
+use rspotify::model::{PlaylistId, TrackId};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct PlaymateConfig {
+    pub playlist_id: Option<PlaylistId>,
+    pub playlist_snapshot_id: Option<String>,
+    pub playlist_track_cache: Option<Vec<TrackId>>,
+}
+
+impl PlaymateConfig {
+    fn new() -> PlaymateConfig {
+        PlaymateConfig {
+            playlist_id: None,
+            playlist_snapshot_id: None,
+            playlist_track_cache: None,
+        }
+    }
+
+    fn read_or_create_config_file(profile: &String) -> String {
+        // Build the config string
+        let config_path = config_file_path(profile);
+
+        // Check if the config file exists
+        if fs::metadata(&config_path).is_err() {
+            println!("Config file not found, creating new one");
+            // Create the config file
+            fs::create_dir_all(
+                config_path
+                    .parent()
+                    .expect("Error getting config path parent"),
+            )
+            .expect("Error creating config directory");
+            fs::File::create(&config_path).expect("Error creating config file");
+        }
+
+        // Read the file
+        fs::read_to_string(config_path).expect("Unable to read config file")
+    }
+
+    pub fn load(profile: &String) -> Self {
+        let config_str = Self::read_or_create_config_file(profile);
+        let config: PlaymateConfig = toml::from_str(&config_str).unwrap();
+        config
+    }
+
+    pub fn save(&self, profile: &String) {
+        let config_str = toml::to_string(&self).unwrap();
+        fs::write(config_file_path(profile), config_str).expect("Error writing config file");
+    }
+}
+
+/// Returns the platform-appropriate base directory for playmate's config and token cache, e.g.
+/// `~/.config/playmate` on Linux, `~/Library/Application Support/playmate` on macOS, or
+/// `%APPDATA%\playmate` on Windows.
+///
+/// We use `BaseDirs::config_dir()` rather than `ProjectDirs`, since `ProjectDirs` tacks on an
+/// extra `config` segment on Windows (`%APPDATA%\playmate\config`); joining `playmate` onto the
+/// bare OS config directory ourselves matches the baseline's `%APPDATA%\playmate` layout so
+/// existing Windows installs don't lose their cached token or playlist selection.
+pub fn base_dir() -> PathBuf {
+    BaseDirs::new()
+        .expect("Could not determine a config directory for this platform")
+        .config_dir()
+        .join("playmate")
+}
+
+fn config_file_path(profile: &String) -> PathBuf {
+    base_dir().join(profile).join("config.toml") // This allows for custom profiles in the future
+}
@@ -1,142 +1,221 @@
-use std::{env, fs, io::Write, path::Path, sync::Arc};
+mod auth;
+mod config;
+mod retry;
+mod setops;
+mod watch;
 
-use clap::Parser;
-use dotenv_codegen::dotenv;
-use futures::StreamExt;
+use std::io::Write;
+
+use clap::{Parser, Subcommand};
+use config::PlaymateConfig;
 use rspotify::{
-    clients::mutex::Mutex,
     model::{Id, PlaylistId, TrackId},
     prelude::{OAuthClient, PlayableId},
-    scopes, AuthCodeSpotify, Config, Credentials, OAuth,
+    AuthCodeSpotify, ClientResult,
 };
-use serde::{Deserialize, Serialize};
+use setops::SetOpKind;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[arg(short, long, default_value = "default")]
     profile: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct PlaymateConfig {
-    playlist_id: Option<PlaylistId>,
-    playlist_snapshot_id: Option<String>,
-    playlist_track_cache: Option<Vec<TrackId>>,
-}
-
-impl PlaymateConfig {
-    fn new() -> PlaymateConfig {
-        PlaymateConfig {
-            playlist_id: None,
-            playlist_snapshot_id: None,
-            playlist_track_cache: None,
-        }
-    }
 
-    fn read_or_create_config_file(profile: &String) -> String {
-        // Build the config string
-        let appdata_dir = env::var_os("APPDATA").expect("No APPDATA environment variable?");
-        let config_path = Path::new(&appdata_dir)
-            .join("playmate")
-            .join(profile) // This allows for custom profiles in the future
-            .join("config.toml");
-
-        // Check if the config file exists
-        if fs::metadata(&config_path).is_err() {
-            println!("Config file not found, creating new one");
-            // Create the config file
-            fs::create_dir_all(
-                &config_path
-                    .parent()
-                    .expect("Error getting config path parent"),
-            )
-            .expect("Error creating config directory");
-            fs::File::create(&config_path).expect("Error creating config file");
-        }
+    /// Run continuously instead of syncing once, moving the track whenever it changes.
+    #[arg(long)]
+    watch: bool,
 
-        // Read the file
-        fs::read_to_string(config_path).expect("Unable to read config file")
-    }
+    /// Polling interval in seconds, used with --watch.
+    #[arg(long, default_value_t = 5)]
+    interval: u64,
 
-    fn load(profile: &String) -> Self {
-        let config_str = Self::read_or_create_config_file(profile);
-        let config: PlaymateConfig = toml::from_str(&config_str).unwrap();
-        config
-    }
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
 
-    fn save(&self, profile: &String) {
-        let config_str = toml::to_string(&self).unwrap();
-        let appdata_dir = env::var_os("APPDATA").expect("No APPDATA environment variable?");
-        let config_path = Path::new(&appdata_dir)
-            .join("playmate")
-            .join(profile)
-            .join("config.toml");
-        fs::write(config_path, config_str).expect("Error writing config file");
-    }
+#[derive(Subcommand)]
+enum Commands {
+    /// Compute a set operation (intersect/union/difference) across playlists.
+    SetOp {
+        #[arg(value_enum)]
+        op: SetOpKind,
+        /// Source playlist IDs, in order; the first playlist's order is preserved in the result.
+        #[arg(required = true, num_args = 2..)]
+        sources: Vec<String>,
+        /// Playlist ID to write the result into.
+        target: String,
+    },
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    let spotify = auth::spotify_auth().await;
+
+    if let Some(Commands::SetOp {
+        op,
+        sources,
+        target,
+    }) = cli.command
+    {
+        setops::run(&spotify, op, sources, target).await;
+        return;
+    }
+
     let mut config = PlaymateConfig::load(&cli.profile);
-    let spotify = spotify_auth().await;
     if config.playlist_id.is_none() {
         config.playlist_id = Some(fetch_playlist_id(&spotify).await);
         config.save(&cli.profile);
     }
+    let playlist_id = config.playlist_id.clone().unwrap();
+
+    if cli.watch {
+        watch::run(
+            &spotify,
+            &mut config,
+            &cli.profile,
+            &playlist_id,
+            cli.interval,
+        )
+        .await;
+        return;
+    }
 
-    // Get the currently playing track
-    let current_track = spotify
-        .current_user_playing_item()
+    match fetch_current_track(&spotify)
         .await
-        .expect("Error getting current track");
-    if current_track.is_none() {
-        println!("No track is playing");
-        return;
+        .expect("Error getting current track")
+    {
+        CurrentTrack::None => println!("No track is playing"),
+        CurrentTrack::Local => {
+            println!("The current track is local, so it cannot be added to the playlist")
+        }
+        CurrentTrack::Track(id) => {
+            move_track_to_playlist(&spotify, &mut config, &playlist_id, id)
+                .await
+                .expect("Error moving current track to playlist");
+            config.save(&cli.profile);
+        }
     }
+}
 
-    // Get the id of the current track
-    let current_track_id = current_track
-        .unwrap()
-        .item
-        .expect("Unable to get currently playing item");
-    let current_track_id = current_track_id.id();
+/// The currently playing item, boiled down to what the rest of the tool cares about.
+pub(crate) enum CurrentTrack {
+    /// Nothing is currently playing.
+    None,
+    /// A local file is playing, which has no Spotify id and can't be added to a playlist.
+    Local,
+    Track(PlayableId),
+}
 
-    if current_track_id.is_none() {
-        println!("The current track is local, so it cannot be added to the playlist");
-        return;
-    }
-    // Remove the current track from the playlist
-    let _snapshot_id = spotify
-        .playlist_remove_all_occurrences_of_items(
-            &config.playlist_id.clone().unwrap(),
-            current_track_id,
-            None,
-        )
-        .await
-        .expect("Error removing current track from playlist")
+/// Fetches the user's currently playing item and classifies it as [`CurrentTrack`].
+pub(crate) async fn fetch_current_track(spotify: &AuthCodeSpotify) -> ClientResult<CurrentTrack> {
+    let current_track = retry::with_retry(|| spotify.current_user_playing_item()).await?;
+    let Some(current_track) = current_track else {
+        return Ok(CurrentTrack::None);
+    };
+    // `item` is `None` during ad breaks (free tier) and for item types we didn't ask for via
+    // `additional_types` — treat that the same as nothing playing rather than panicking.
+    let Some(item) = current_track.item else {
+        return Ok(CurrentTrack::None);
+    };
+    Ok(match item.id() {
+        Some(id) => CurrentTrack::Track(id),
+        None => CurrentTrack::Local,
+    })
+}
+
+/// Removes `current_track_id` from the playlist (if our cache says it's already there) and
+/// re-adds it, updating `config`'s snapshot id and track cache to match.
+pub(crate) async fn move_track_to_playlist(
+    spotify: &AuthCodeSpotify,
+    config: &mut PlaymateConfig,
+    playlist_id: &PlaylistId,
+    current_track_id: PlayableId,
+) -> ClientResult<()> {
+    let track_cache = refresh_track_cache(spotify, playlist_id, config).await?;
+    let already_in_playlist = track_cache
+        .iter()
+        .any(|id| PlayableId::Track(id.clone()) == current_track_id);
+
+    // Remove the current track from the playlist, but only if our cache says it's actually there
+    if already_in_playlist {
+        let snapshot_id = retry::with_retry(|| {
+            spotify.playlist_remove_all_occurrences_of_items(
+                playlist_id,
+                Some(current_track_id.clone()),
+                None,
+            )
+        })
+        .await?
         .snapshot_id;
+        config.playlist_snapshot_id = Some(snapshot_id);
+    } else {
+        println!("Current track isn't in the playlist yet, skipping removal");
+    }
+
     // Add the current track to the playlist
-    let _snapshot_id = spotify
-        .playlist_add_items(&config.playlist_id.unwrap(), current_track_id, None)
-        .await
-        .expect("Error adding current track to playlist")
-        .snapshot_id;
+    let snapshot_id = retry::with_retry(|| {
+        spotify.playlist_add_items(playlist_id, Some(current_track_id.clone()), None)
+    })
+    .await?
+    .snapshot_id;
+
+    // Keep the local cache in sync with what we just did, so the next run doesn't need to
+    // re-fetch the whole playlist.
+    let mut updated_cache: Vec<TrackId> = track_cache
+        .into_iter()
+        .filter(|id| PlayableId::Track(id.clone()) != current_track_id)
+        .collect();
+    if let PlayableId::Track(id) = current_track_id {
+        updated_cache.push(id);
+    }
+    config.playlist_snapshot_id = Some(snapshot_id);
+    config.playlist_track_cache = Some(updated_cache);
+    Ok(())
+}
+
+/// Returns the playlist's current track ids, re-fetching them only if the playlist's
+/// `snapshot_id` has changed since the last run. Updates `config` in place with the fresh
+/// snapshot id and track cache when a re-fetch happens.
+pub(crate) async fn refresh_track_cache(
+    spotify: &AuthCodeSpotify,
+    playlist_id: &PlaylistId,
+    config: &mut PlaymateConfig,
+) -> ClientResult<Vec<TrackId>> {
+    let current_snapshot_id =
+        retry::with_retry(|| spotify.playlist(playlist_id.clone(), None, None))
+            .await?
+            .snapshot_id;
+
+    if config.playlist_snapshot_id.as_ref() == Some(&current_snapshot_id) {
+        if let Some(cache) = &config.playlist_track_cache {
+            return Ok(cache.clone());
+        }
+    }
+
+    println!("Playlist cache is stale, re-fetching track list");
+    let tracks = retry::fetch_playlist_track_ids(spotify, playlist_id).await?;
+
+    config.playlist_snapshot_id = Some(current_snapshot_id);
+    config.playlist_track_cache = Some(tracks.clone());
+    Ok(tracks)
 }
 
 async fn fetch_playlist_id(spotify: &AuthCodeSpotify) -> PlaylistId {
-    let playlists = spotify.current_user_playlists().collect::<Vec<_>>().await;
+    let playlists = retry::fetch_all_paginated(|offset, limit| async move {
+        spotify
+            .current_user_playlists_manual(Some(limit), Some(offset))
+            .await
+            .map(|page| page.items)
+    })
+    .await
+    .expect("Error fetching playlists");
 
     // Print the playlist and prompt the user to select one
     loop {
         println!("Select a playlist");
         for (count, p) in playlists.iter().enumerate() {
-            println!(
-                "{:>4}: {}",
-                count + 1,
-                p.as_ref().expect("Error iterating over playlists").name
-            );
+            println!("{:>4}: {}", count + 1, p.name);
         }
         print!(
             "Select which playlist to use by typing the playlist's number and pressing enter:\n> "
@@ -159,59 +238,6 @@ async fn fetch_playlist_id(spotify: &AuthCodeSpotify) -> PlaylistId {
             println!("Invalid selection\n");
             continue;
         }
-        return playlists[selection - 1]
-            .as_ref()
-            .expect("Error selecting playlist")
-            .id
-            .clone();
-    }
-}
-
-async fn spotify_auth() -> AuthCodeSpotify {
-    // let creds = Credentials::from_env().expect("Failed to get app credentials");
-    let creds = Credentials::new(
-        dotenv!("RSPOTIFY_CLIENT_ID"),
-        dotenv!("RSPOTIFY_CLIENT_SECRET"),
-    );
-    let oauth = OAuth {
-        redirect_uri: "http://localhost:8888/callback".to_string(),
-        scopes: scopes!(
-            "user-read-currently-playing",
-            "user-read-playback-state",
-            "playlist-read-private",
-            "playlist-modify-private",
-            "user-library-modify",
-            "user-library-read"
-        ),
-        ..Default::default()
-    };
-    let appdata_dir = env::var_os("APPDATA").expect("No APPDATA environment variable?");
-    let config = Config {
-        token_cached: true,
-        token_refreshing: true,
-        cache_path: Path::new(&appdata_dir)
-            .join("playmate")
-            .join("token_cache.json"),
-        // pagination_chunks: 100,
-        ..Default::default()
-    };
-    let mut spotify = AuthCodeSpotify::with_config(creds, oauth, config);
-    if !spotify.config.cache_path.exists() {
-        println!(
-            "A browser window will open to prompt you to log in to Spotify. \
-        Once you have logged in, it will redirect you to a page that will show you an error. \
-        This is expected. Copy the URL of the page and paste it into the terminal.\
-        \nPress enter to continue. "
-        );
-        std::io::stdin()
-            .read_line(&mut String::new())
-            .expect("Error reading enter");
-        let url = spotify.get_authorize_url(false).unwrap();
-        spotify
-            .prompt_for_token(&url)
-            .await
-            .expect("Couldn't authenticate successfully");
+        return playlists[selection - 1].id.clone();
     }
-    spotify.token = Arc::new(Mutex::new(spotify.read_token_cache(true).await.unwrap()));
-    spotify
 }
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use rspotify::{
+    http::HttpError,
+    model::{PlayableItem, PlaylistId, TrackId},
+    prelude::OAuthClient,
+    AuthCodeSpotify, ClientError, ClientResult,
+};
+
+/// Maximum number of attempts before giving up on a rate-limited request.
+const MAX_RETRIES: u32 = 5;
+
+/// Fallback delay used when Spotify's `Retry-After` header is missing or unparsable.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Number of items requested per page when draining a paginated endpoint.
+pub const PAGE_SIZE: u32 = 50;
+
+/// Runs `request`, transparently retrying on HTTP 429 responses.
+///
+/// On a rate limit, the `Retry-After` header (seconds) is read and the task sleeps for that
+/// long before retrying, up to `MAX_RETRIES` attempts. Any other error is returned immediately.
+pub async fn with_retry<F, Fut, T>(mut request: F) -> ClientResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = ClientResult<T>>,
+{
+    for attempt in 0..MAX_RETRIES {
+        match request().await {
+            Ok(value) => return Ok(value),
+            Err(ClientError::Http(err)) => {
+                let HttpError::StatusCode(response) = *err else {
+                    return Err(ClientError::Http(err));
+                };
+                if response.status().as_u16() != 429 || attempt + 1 == MAX_RETRIES {
+                    return Err(ClientError::Http(Box::new(HttpError::StatusCode(response))));
+                }
+                let retry_after = response
+                    .headers()
+                    .get("Retry-After")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_RETRY_AFTER);
+                println!(
+                    "Rate limited by Spotify, retrying in {} seconds (attempt {}/{})",
+                    retry_after.as_secs(),
+                    attempt + 1,
+                    MAX_RETRIES
+                );
+                tokio::time::sleep(retry_after).await;
+            }
+            Err(other) => return Err(other),
+        }
+    }
+    unreachable!("loop always returns before exhausting its iteration count")
+}
+
+/// Drains a paginated endpoint in chunks of [`PAGE_SIZE`], calling `fetch_page` with successive
+/// offsets until an empty page is returned. Each page fetch goes through [`with_retry`].
+pub async fn fetch_all_paginated<T, F, Fut>(mut fetch_page: F) -> ClientResult<Vec<T>>
+where
+    F: FnMut(u32, u32) -> Fut,
+    Fut: std::future::Future<Output = ClientResult<Vec<T>>>,
+{
+    let mut items = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page = with_retry(|| fetch_page(offset, PAGE_SIZE)).await?;
+        if page.is_empty() {
+            break;
+        }
+        offset += page.len() as u32;
+        items.extend(page);
+    }
+    Ok(items)
+}
+
+/// Fetches a playlist's track ids in order, skipping local tracks and episodes, which have no
+/// [`TrackId`].
+pub async fn fetch_playlist_track_ids(
+    spotify: &AuthCodeSpotify,
+    playlist_id: &PlaylistId,
+) -> ClientResult<Vec<TrackId>> {
+    let tracks = fetch_all_paginated(|offset, limit| async move {
+        spotify
+            .playlist_items_manual(playlist_id.clone(), None, None, Some(limit), Some(offset))
+            .await
+            .map(|page| page.items)
+    })
+    .await?
+    .into_iter()
+    .filter_map(|item| match item.track {
+        Some(PlayableItem::Track(track)) => track.id,
+        _ => None,
+    })
+    .collect();
+    Ok(tracks)
+}
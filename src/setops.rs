@@ -0,0 +1,103 @@
+use std::collections::HashSet;
+
+use clap::ValueEnum;
+use rspotify::{
+    model::{Id, PlayableId, PlaylistId, TrackId},
+    prelude::OAuthClient,
+    AuthCodeSpotify,
+};
+
+use crate::retry;
+
+/// Spotify's limit on tracks per `playlist_add_items` call.
+const ADD_BATCH_SIZE: usize = 100;
+
+#[derive(Clone, ValueEnum)]
+pub enum SetOpKind {
+    /// Keep tracks that appear in every source playlist.
+    Intersect,
+    /// Keep tracks of the first source playlist that don't appear in any other source.
+    Difference,
+    /// Keep every distinct track across all source playlists.
+    Union,
+}
+
+/// Computes `op` over `sources` (in order) and writes the result into `target`, which may be a
+/// new or existing playlist. The first source playlist's track order is preserved in the result.
+pub async fn run(spotify: &AuthCodeSpotify, op: SetOpKind, sources: Vec<String>, target: String) {
+    let sources: Vec<PlaylistId> = sources
+        .into_iter()
+        .map(|id| PlaylistId::from_id(id).expect("Invalid source playlist id"))
+        .collect();
+    let target = PlaylistId::from_id(target).expect("Invalid target playlist id");
+
+    let mut track_lists = Vec::with_capacity(sources.len());
+    for playlist_id in &sources {
+        track_lists.push(fetch_track_ids(spotify, playlist_id).await);
+    }
+
+    let result = match op {
+        SetOpKind::Intersect => intersect(&track_lists),
+        SetOpKind::Difference => difference(&track_lists),
+        SetOpKind::Union => union(&track_lists),
+    };
+
+    println!("Writing {} tracks to the target playlist", result.len());
+    // Replace the target's existing contents with the first batch (or clear it if the result is
+    // empty), then append the rest, so re-running a set operation doesn't pile on top of what's
+    // already there.
+    let mut chunks = result.chunks(ADD_BATCH_SIZE);
+    let first_chunk = chunks.next().unwrap_or_default();
+    let first_items = first_chunk.iter().cloned().map(PlayableId::Track);
+    retry::with_retry(|| spotify.playlist_replace_items(&target, first_items.clone()))
+        .await
+        .expect("Error replacing target playlist contents");
+    for chunk in chunks {
+        let items = chunk.iter().cloned().map(PlayableId::Track);
+        retry::with_retry(|| spotify.playlist_add_items(&target, items.clone(), None))
+            .await
+            .expect("Error adding tracks to target playlist");
+    }
+}
+
+/// Fetches a playlist's track ids in order, skipping local tracks, which have no id.
+async fn fetch_track_ids(spotify: &AuthCodeSpotify, playlist_id: &PlaylistId) -> Vec<TrackId> {
+    retry::fetch_playlist_track_ids(spotify, playlist_id)
+        .await
+        .expect("Error fetching playlist tracks")
+}
+
+fn intersect(lists: &[Vec<TrackId>]) -> Vec<TrackId> {
+    let Some((first, rest)) = lists.split_first() else {
+        return Vec::new();
+    };
+    let others: Vec<HashSet<&TrackId>> = rest.iter().map(|list| list.iter().collect()).collect();
+    first
+        .iter()
+        .filter(|id| others.iter().all(|set| set.contains(id)))
+        .cloned()
+        .collect()
+}
+
+fn difference(lists: &[Vec<TrackId>]) -> Vec<TrackId> {
+    let Some((first, rest)) = lists.split_first() else {
+        return Vec::new();
+    };
+    let others: HashSet<&TrackId> = rest.iter().flatten().collect();
+    first
+        .iter()
+        .filter(|id| !others.contains(id))
+        .cloned()
+        .collect()
+}
+
+fn union(lists: &[Vec<TrackId>]) -> Vec<TrackId> {
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    for id in lists.iter().flatten() {
+        if seen.insert(id.clone()) {
+            result.push(id.clone());
+        }
+    }
+    result
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use rspotify::{model::PlaylistId, AuthCodeSpotify};
+
+use crate::{config::PlaymateConfig, CurrentTrack};
+
+/// Polls the currently playing track on an interval and moves it to the playlist whenever it
+/// changes, skipping the playlist update entirely when the same track is still playing. Runs
+/// forever, making this suitable as a long-lived background process. A failed request is logged
+/// and skipped rather than ending the loop, since transient API errors are expected over a long
+/// enough run.
+pub async fn run(
+    spotify: &AuthCodeSpotify,
+    config: &mut PlaymateConfig,
+    profile: &String,
+    playlist_id: &PlaylistId,
+    interval_secs: u64,
+) {
+    let mut last_seen_track_id = None;
+
+    loop {
+        match crate::fetch_current_track(spotify).await {
+            Err(err) => eprintln!("Error getting current track, will retry next interval: {err}"),
+            Ok(CurrentTrack::None) => {
+                if last_seen_track_id.take().is_some() {
+                    println!("No track is playing");
+                }
+            }
+            Ok(CurrentTrack::Local) => {
+                if last_seen_track_id.take().is_some() {
+                    println!("The current track is local, so it cannot be added to the playlist");
+                }
+            }
+            Ok(CurrentTrack::Track(id)) if last_seen_track_id.as_ref() == Some(&id) => {
+                // Same song still playing, nothing to do.
+            }
+            Ok(CurrentTrack::Track(id)) => {
+                match crate::move_track_to_playlist(spotify, config, playlist_id, id.clone()).await
+                {
+                    Ok(()) => {
+                        config.save(profile);
+                        last_seen_track_id = Some(id);
+                    }
+                    Err(err) => {
+                        eprintln!("Error moving current track to playlist, will retry next interval: {err}");
+                    }
+                }
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+    }
+}